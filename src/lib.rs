@@ -4,6 +4,11 @@ extern crate raqm_sys;
 
 use std::os::raw::c_int;
 
+mod line_layout;
+pub use line_layout::{
+    HorizontalAlign, Line, LineLayout, VerticalAlign, WrapStyle,
+};
+
 #[derive(Debug, Fail)]
 pub enum RaqmError {
     #[fail(display = "raqm_create() returned NULL")]
@@ -13,21 +18,42 @@ pub enum RaqmError {
     // TODO: sensible errors if that's possible with libraqm
     #[fail(display = "libraqm error")]
     Failed,
+    #[fail(display = "range {}..{} is inverted", start, end)]
+    RangeInverted { start: usize, end: usize },
+    #[fail(display = "range {}..{} is out of bounds for text of length {}", start, end, len)]
+    RangeOutOfBounds {
+        start: usize,
+        end: usize,
+        len: usize,
+    },
+    #[fail(display = "range {}..{} does not fall on UTF-8 character boundaries", start, end)]
+    RangeNotCharBoundary { start: usize, end: usize },
+    #[fail(display = "face ranges leave the text uncovered at index {}", at)]
+    FaceCoverageGap { at: usize },
+    #[fail(display = "font feature tag {:?} must be exactly four ASCII bytes", tag)]
+    InvalidFeatureTag { tag: String },
 }
 
 pub type Result<T> = ::std::result::Result<T, RaqmError>;
 
 // Import functions
 use raqm_sys::{
-    raqm_add_font_feature, raqm_create, raqm_destroy, raqm_get_glyphs, raqm_index_to_position,
-    raqm_layout, raqm_position_to_index, raqm_set_freetype_face, raqm_set_freetype_face_range,
-    raqm_set_freetype_load_flags, raqm_set_language, raqm_set_par_direction, raqm_set_text,
+    raqm_add_font_feature, raqm_clear_contents, raqm_create, raqm_destroy, raqm_get_glyphs,
+    raqm_index_to_position,
+    raqm_get_direction_at_index, raqm_get_par_resolved_direction, raqm_layout,
+    raqm_position_to_index, raqm_set_freetype_face, raqm_set_freetype_face_range,
+    raqm_set_freetype_load_flags, raqm_set_harfbuzz_font, raqm_set_harfbuzz_font_range,
+    raqm_set_language, raqm_set_par_direction, raqm_set_text, raqm_set_text_utf16,
     raqm_set_text_utf8,
 };
 
 // Import types
 use raqm_sys::{raqm_glyph_t, raqm_t, FT_Face};
 
+/// The HarfBuzz font handle type, re-exported so callers can hand an
+/// `hb_font_t` they already own to `set_harfbuzz_font`.
+pub use raqm_sys::hb_font_t;
+
 use std::borrow::Borrow;
 
 macro_rules! check_success {
@@ -55,6 +81,22 @@ impl Raqm {
         }
     }
 
+    /// Resets the text and all attribute state of this instance while keeping
+    /// the allocation for reuse with the next paragraph, avoiding a
+    /// raqm_create()/raqm_destroy() round-trip per layout. Useful for
+    /// applications that lay out many short strings (labels, list items,
+    /// incremental editor relayout).
+    ///
+    /// After clear() the instance behaves like a freshly created one: all prior
+    /// text, face, language and feature ranges are dropped.
+    ///
+    /// Requires libraqm 0.9.0 or newer (raqm_clear_contents).
+    pub fn clear(&mut self) -> Result<()> {
+        check_success!(unsafe {
+            raqm_clear_contents(self.ptr)
+        })
+    }
+
     /// Adds text to rq to be used for layout. It must be a valid UTF-32 text,
     /// any invalid character will be replaced with U+FFFD.
     /// The text should typically represent a full paragraph,
@@ -74,6 +116,19 @@ impl Raqm {
         })
     }
 
+    /// Same as Raqm::set_text_utf32(), but for text encoded in UTF-16 encoding. Useful for callers
+    /// interoperating with UTF-16 sources (Windows APIs, JavaScript/DOM strings, ICU buffers) without
+    /// a lossy transcode to UTF-8 first.
+    ///
+    /// Note that for UTF-16 the start and end indices passed to set_language() and
+    /// set_freetype_face_range() count UTF-16 code units, matching raqm's indexing convention for the
+    /// chosen encoding.
+    pub fn set_text_utf16(&mut self, text: &[u16]) -> Result<()> {
+        check_success!(unsafe {
+            raqm_set_text_utf16(self.ptr, text.as_ptr(), text.len())
+        })
+    }
+
     /// Sets the paragraph direction, also known as block direction in CSS.
     /// For horizontal text, this controls the overall direction in the Unicode Bidirectional Algorithm,
     /// so when the text is mainly right-to-left (with or without some left-to-right) text,
@@ -129,6 +184,34 @@ impl Raqm {
         })
     }
 
+    /// Sets an hb_font_t to be used for all characters in rq.
+    ///
+    /// Unlike set_freetype_face(), which makes raqm create a fresh HarfBuzz font from the FT_Face on
+    /// every layout, this lets callers pass an hb_font_t they already own — e.g. one configured with
+    /// variation-axis coordinates for a variable font, or with a pre-warmed shaping cache.
+    pub fn set_harfbuzz_font(&mut self, font: *mut hb_font_t) -> Result<()> {
+        check_success!(unsafe {
+            raqm_set_harfbuzz_font(self.ptr, font)
+        })
+    }
+
+    /// Sets an hb_font_t to be used for len -number of characters staring at start.
+    /// The start and len are input string array indices (i.e. counting bytes in UTF-8 and scalar
+    /// values in UTF-32), matching the semantics of set_freetype_face_range().
+    ///
+    /// This method can be used repeatedly to set different fonts for different parts of the text.
+    /// It is the responsibility of the client to make sure that font ranges cover the whole text.
+    pub fn set_harfbuzz_font_range(
+        &mut self,
+        font: *mut hb_font_t,
+        start: usize,
+        end: usize,
+    ) -> Result<()> {
+        check_success!(unsafe {
+            raqm_set_harfbuzz_font_range(self.ptr, font, start, end)
+        })
+    }
+
     /// Sets the load flags passed to FreeType when loading glyphs, should be the same flags used by
     /// the client when rendering FreeType glyphs.
     //
@@ -144,13 +227,20 @@ impl Raqm {
     /// on optional font features that are not enabled by default, for example dlig or ss01,
     /// but can be also used to turn off default font features.
     ///
-    /// feature is string representing a single font feature, in the syntax understood by hb_feature_from_string().
-    //
+    /// The feature is built through the typed `FontFeature` constructor, which is serialized to the
+    /// HarfBuzz feature string understood by hb_feature_from_string() internally, so callers never
+    /// hand-build `"ss01=1"` nor manage the trailing length argument.
+    ///
     /// This function can be called repeatedly, new features will be appended to the end of the
     /// features list and can potentially override previous features.
-    pub fn add_font_feature(&mut self, feature: &str, len: usize) -> Result<()> {
+    pub fn add_font_feature(&mut self, feature: &FontFeature) -> Result<()> {
+        let string = feature.to_feature_string();
         check_success!(unsafe {
-            raqm_add_font_feature(self.ptr, feature.as_ptr() as *const i8, len as c_int)
+            raqm_add_font_feature(
+                self.ptr,
+                string.as_ptr() as *const i8,
+                string.len() as c_int,
+            )
         })
     }
 
@@ -219,6 +309,28 @@ impl Raqm {
         })
     }
 
+    /// Returns the paragraph direction the engine resolved during layout().
+    ///
+    /// When set_par_direction(Direction::Default) is used, raqm auto-detects the direction from the
+    /// first strong character; this reports the direction actually chosen, so callers drawing cursors
+    /// or selection highlights know how the paragraph resolved.
+    pub fn resolved_par_direction(&self) -> Result<Direction> {
+        let mut direction = raqm_sys::raqm_direction_t_RAQM_DIRECTION_DEFAULT;
+        check_success!(unsafe { raqm_get_par_resolved_direction(self.ptr, &mut direction) })
+            .map(|_| Direction::from_raw(direction))
+    }
+
+    /// Returns the resolved direction of the cluster at index after layout().
+    ///
+    /// This is the per-cluster counterpart of resolved_par_direction(): in a bidi paragraph
+    /// different clusters resolve to different directions, which matters when placing carets and
+    /// selection rectangles next to the positions from index_to_position()/position_to_index().
+    pub fn direction_at(&self, index: usize) -> Result<Direction> {
+        let mut direction = raqm_sys::raqm_direction_t_RAQM_DIRECTION_DEFAULT;
+        check_success!(unsafe { raqm_get_direction_at_index(self.ptr, index, &mut direction) })
+            .map(|_| Direction::from_raw(direction))
+    }
+
     /// Returns the index of the character at x and y position within text.
     /// If the position is outside the text, the last character is chosen as index.
     pub fn position_to_index(&mut self, x: i32, y: i32) -> Result<usize> {
@@ -245,6 +357,240 @@ pub enum Direction {
     TopToBottom = raqm_sys::raqm_direction_t_RAQM_DIRECTION_TTB as isize,
 }
 
+impl Direction {
+    fn from_raw(direction: raqm_sys::raqm_direction_t) -> Direction {
+        match direction {
+            raqm_sys::raqm_direction_t_RAQM_DIRECTION_RTL => Direction::RightToLeft,
+            raqm_sys::raqm_direction_t_RAQM_DIRECTION_LTR => Direction::LeftToRight,
+            raqm_sys::raqm_direction_t_RAQM_DIRECTION_TTB => Direction::TopToBottom,
+            _ => Direction::Default,
+        }
+    }
+}
+
+/// A single OpenType font feature, built with a typed tag instead of a raw
+/// HarfBuzz feature string. Use `Raqm::add_font_feature` to apply it.
+///
+/// ```ignore
+/// raqm.add_font_feature(&FontFeature::new("ss01")?.enabled(true))?;
+/// raqm.add_font_feature(&FontFeature::new("aalt")?.value(2).range(3..5))?;
+/// ```
+pub struct FontFeature {
+    tag: [u8; 4],
+    value: u32,
+    range: Option<std::ops::Range<usize>>,
+}
+
+impl FontFeature {
+    /// Creates a feature for the four-byte OpenType `tag` (e.g. `dlig`, `ss01`,
+    /// `kern`), enabled with a value of `1` over the whole text. Returns an
+    /// error if the tag is not exactly four ASCII bytes.
+    pub fn new(tag: &str) -> Result<Self> {
+        let bytes = tag.as_bytes();
+        if bytes.len() != 4 || !bytes.iter().all(u8::is_ascii) {
+            return Err(RaqmError::InvalidFeatureTag {
+                tag: tag.to_owned(),
+            });
+        }
+        let mut stored = [0u8; 4];
+        stored.copy_from_slice(bytes);
+        Ok(FontFeature {
+            tag: stored,
+            value: 1,
+            range: None,
+        })
+    }
+
+    /// Turns the feature on (`value = 1`) or off (`value = 0`).
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.value = enabled as u32;
+        self
+    }
+
+    /// Sets an explicit feature value, for features that select one of several
+    /// alternates (e.g. stylistic alternate sets).
+    pub fn value(mut self, value: u32) -> Self {
+        self.value = value;
+        self
+    }
+
+    /// Scopes the feature to the characters in `range` instead of the whole
+    /// text. Offsets follow the same unit as the text encoding.
+    pub fn range(mut self, range: std::ops::Range<usize>) -> Self {
+        self.range = Some(range);
+        self
+    }
+
+    /// Serializes to the `tag[start:end]=value` form understood by
+    /// hb_feature_from_string().
+    fn to_feature_string(&self) -> String {
+        // `tag` is validated to be four ASCII bytes in `new`.
+        let tag = std::str::from_utf8(&self.tag).unwrap();
+        match &self.range {
+            Some(range) => format!("{}[{}:{}]={}", tag, range.start, range.end, self.value),
+            None => format!("{}={}", tag, self.value),
+        }
+    }
+}
+
+/// The text source a `TextBuilder` lays out, carrying the encoding so that
+/// range offsets can be validated with the right unit (bytes for UTF-8,
+/// scalar values for UTF-32).
+enum TextSource<'a> {
+    Utf8(&'a str),
+    Utf32(&'a [u32]),
+}
+
+impl<'a> TextSource<'a> {
+    fn len(&self) -> usize {
+        match self {
+            TextSource::Utf8(s) => s.len(),
+            TextSource::Utf32(s) => s.len(),
+        }
+    }
+}
+
+/// Builder that unifies the text and its per-range attributes into a single,
+/// checked step, replacing the error-prone sequence of manual index-based
+/// `set_text`/`set_freetype_face_range`/`set_language` calls.
+///
+/// Offsets are interpreted in the unit matching the text encoding: byte
+/// offsets for UTF-8 (`TextBuilder::new`) and scalar indices for UTF-32
+/// (`TextBuilder::new_utf32`), the same convention as the `Raqm` setters.
+pub struct TextBuilder<'a> {
+    source: TextSource<'a>,
+    direction: Option<Direction>,
+    faces: Vec<(FT_Face, std::ops::Range<usize>)>,
+    languages: Vec<(&'a str, std::ops::Range<usize>)>,
+    require_face_coverage: bool,
+}
+
+impl<'a> TextBuilder<'a> {
+    /// Starts a builder for UTF-8 `text`. Range offsets count bytes.
+    pub fn new(text: &'a str) -> Self {
+        TextBuilder::with_source(TextSource::Utf8(text))
+    }
+
+    /// Starts a builder for UTF-32 `text`. Range offsets count scalar values.
+    pub fn new_utf32(text: &'a [u32]) -> Self {
+        TextBuilder::with_source(TextSource::Utf32(text))
+    }
+
+    fn with_source(source: TextSource<'a>) -> Self {
+        TextBuilder {
+            source,
+            direction: None,
+            faces: Vec::new(),
+            languages: Vec::new(),
+            require_face_coverage: false,
+        }
+    }
+
+    /// Sets the paragraph direction for the whole text.
+    pub fn direction(mut self, direction: Direction) -> Self {
+        self.direction = Some(direction);
+        self
+    }
+
+    /// Attaches an FT_Face to the characters in `range`.
+    pub fn face_range(mut self, face: FT_Face, range: std::ops::Range<usize>) -> Self {
+        self.faces.push((face, range));
+        self
+    }
+
+    /// Attaches a BCP47 language code to the characters in `range`.
+    pub fn language_range(mut self, lang: &'a str, range: std::ops::Range<usize>) -> Self {
+        self.languages.push((lang, range));
+        self
+    }
+
+    /// Requires the attached face ranges to cover the whole text. raqm needs
+    /// this today but silently misbehaves when it is not met, so enabling this
+    /// turns the mistake into a typed error at `build_into` time.
+    pub fn require_face_coverage(mut self) -> Self {
+        self.require_face_coverage = true;
+        self
+    }
+
+    /// Issues the underlying `set_text`/`set_freetype_face_range`/
+    /// `set_language` calls in the right order after validating every range,
+    /// returning the first offending range as a typed error.
+    pub fn build_into(self, raqm: &mut Raqm) -> Result<()> {
+        let len = self.source.len();
+
+        for &(_, ref range) in &self.faces {
+            self.check_range(range)?;
+        }
+        for &(_, ref range) in &self.languages {
+            self.check_range(range)?;
+        }
+        if self.require_face_coverage {
+            self.check_face_coverage(len)?;
+        }
+
+        match self.source {
+            TextSource::Utf8(s) => raqm.set_text(s)?,
+            TextSource::Utf32(s) => raqm.set_text_utf32(s)?,
+        }
+
+        if let Some(direction) = self.direction {
+            raqm.set_par_direction(direction)?;
+        }
+        // The raqm setters take a start offset and a length, not an end offset.
+        for (face, range) in self.faces {
+            raqm.set_freetype_face_range(face, range.start, range.end - range.start)?;
+        }
+        for (lang, range) in self.languages {
+            raqm.set_language(lang, range.start, range.end - range.start)?;
+        }
+
+        Ok(())
+    }
+
+    fn check_range(&self, range: &std::ops::Range<usize>) -> Result<()> {
+        let len = self.source.len();
+        if range.start > range.end {
+            return Err(RaqmError::RangeInverted {
+                start: range.start,
+                end: range.end,
+            });
+        }
+        if range.end > len {
+            return Err(RaqmError::RangeOutOfBounds {
+                start: range.start,
+                end: range.end,
+                len,
+            });
+        }
+        if let TextSource::Utf8(s) = self.source {
+            if !s.is_char_boundary(range.start) || !s.is_char_boundary(range.end) {
+                return Err(RaqmError::RangeNotCharBoundary {
+                    start: range.start,
+                    end: range.end,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn check_face_coverage(&self, len: usize) -> Result<()> {
+        let mut ranges: Vec<&std::ops::Range<usize>> = self.faces.iter().map(|(_, r)| r).collect();
+        ranges.sort_by_key(|r| r.start);
+
+        let mut covered = 0;
+        for range in ranges {
+            if range.start > covered {
+                return Err(RaqmError::FaceCoverageGap { at: covered });
+            }
+            covered = covered.max(range.end);
+        }
+        if covered < len {
+            return Err(RaqmError::FaceCoverageGap { at: covered });
+        }
+        Ok(())
+    }
+}
+
 /// Raqm position, representing an index, x and y.
 pub struct Position {
     /// character index
@@ -256,6 +602,7 @@ pub struct Position {
 }
 
 /// The structure that holds information about output glyphs, returned from Raqm::get_glyphs().
+#[derive(Clone)]
 pub struct Glyph {
     /// the index of the glyph in the font file.
     pub index: u32,
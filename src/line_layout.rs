@@ -0,0 +1,520 @@
+//! Multi-line layout on top of a shaped `Raqm` paragraph.
+//!
+//! `Raqm::glyphs()` returns a flat run of shaped glyphs for a single
+//! paragraph. `LineLayout` wraps that run into lines that fit within a
+//! `max_width` (expressed in the same font units as `Glyph::x_advance`),
+//! using the Unicode Line Breaking Algorithm (UAX #14) to decide where a
+//! break is permitted, and a greedy fill to decide where one is taken.
+//!
+//! The break opportunities are computed from the original text and mapped
+//! back onto glyph cluster boundaries (`Glyph::cluster` is the byte offset
+//! of the originating character in UTF-8 text), so wrapping respects both
+//! shaping clusters and Unicode segmentation rules.
+
+use super::Glyph;
+
+/// How a single over-long word is treated when it cannot fit on one line.
+pub enum WrapStyle {
+    /// Only break at UAX #14 break opportunities (between words). A word
+    /// wider than `max_width` overflows its line rather than being split.
+    Word,
+    /// Like `Word`, but when a single word is wider than `max_width` fall
+    /// back to breaking between any two clusters so it never overflows.
+    Letter,
+}
+
+/// Horizontal placement of each line within the `max_width` column.
+pub enum HorizontalAlign {
+    Left,
+    Center,
+    Right,
+}
+
+/// Vertical placement of the whole block of lines relative to its own height.
+pub enum VerticalAlign {
+    Top,
+    Middle,
+    Bottom,
+}
+
+/// One laid-out line: the glyphs that belong to it plus the pen origin at
+/// which the first glyph should be drawn. `x`/`y` already include the
+/// horizontal and vertical alignment offsets.
+pub struct Line {
+    /// The glyphs of this line, in the order they were shaped.
+    pub glyphs: Vec<Glyph>,
+    /// Pen x origin of the line (left edge after horizontal alignment).
+    pub x: i32,
+    /// Pen y origin of the line (top-relative baseline after vertical alignment).
+    pub y: i32,
+}
+
+/// Builder that turns a shaped paragraph into wrapped, aligned lines.
+pub struct LineLayout<'a> {
+    text: &'a str,
+    glyphs: Vec<Glyph>,
+    max_width: i32,
+    line_height: i32,
+    wrap: WrapStyle,
+    h_align: HorizontalAlign,
+    v_align: VerticalAlign,
+}
+
+impl<'a> LineLayout<'a> {
+    /// Creates a layout for `glyphs` (as returned by `Raqm::glyphs()` for
+    /// `text`), wrapping at `max_width` with lines spaced `line_height` apart.
+    pub fn new(text: &'a str, glyphs: Vec<Glyph>, max_width: i32, line_height: i32) -> Self {
+        LineLayout {
+            text,
+            glyphs,
+            max_width,
+            line_height,
+            wrap: WrapStyle::Word,
+            h_align: HorizontalAlign::Left,
+            v_align: VerticalAlign::Top,
+        }
+    }
+
+    /// Sets the wrap style used for words wider than `max_width`.
+    pub fn wrap(mut self, wrap: WrapStyle) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    /// Sets the horizontal alignment applied to every line.
+    pub fn horizontal_align(mut self, align: HorizontalAlign) -> Self {
+        self.h_align = align;
+        self
+    }
+
+    /// Sets the vertical alignment of the block of lines.
+    pub fn vertical_align(mut self, align: VerticalAlign) -> Self {
+        self.v_align = align;
+        self
+    }
+
+    /// Performs the wrapping and returns one `Line` per output row.
+    pub fn lines(self) -> Vec<Line> {
+        let LineLayout {
+            text,
+            glyphs,
+            max_width,
+            line_height,
+            wrap,
+            h_align,
+            v_align,
+        } = self;
+        let breaks = break_opportunities(text);
+
+        // Greedily accumulate glyph advances, remembering the last cluster
+        // boundary at which a break was allowed so we can retreat to it when
+        // the current glyph would overflow the column.
+        let mut rows: Vec<Vec<Glyph>> = Vec::new();
+        let mut current: Vec<Glyph> = Vec::new();
+        let mut width: i32 = 0;
+        let mut last_break: Option<usize> = None; // index into `current`
+        let mut width_at_break: i32 = 0;
+
+        for glyph in glyphs.into_iter() {
+            let offset = glyph.cluster as usize;
+            let kind = breaks.get(&offset).cloned();
+
+            // A mandatory break starts a new line before this glyph.
+            if let Some(BreakKind::Mandatory) = kind {
+                if !current.is_empty() {
+                    rows.push(std::mem::take(&mut current));
+                    width = 0;
+                    last_break = None;
+                    width_at_break = 0;
+                }
+            }
+
+            // Record the break opportunity *before* this glyph before testing
+            // for overflow, so a word-initial glyph that triggers the overflow
+            // can retreat to its own break rather than the previous word's.
+            if let Some(BreakKind::Allowed) = kind {
+                last_break = Some(current.len());
+                width_at_break = width;
+            }
+
+            let advance = glyph.x_advance;
+            if width + advance > max_width && !current.is_empty() {
+                match last_break {
+                    // A break recorded before the first glyph of the row would
+                    // only split off an empty prefix, so ignore it and fall
+                    // through to the overflow handling below.
+                    Some(at) if at > 0 => {
+                        let rest = current.split_off(at);
+                        rows.push(std::mem::replace(&mut current, rest));
+                        width -= width_at_break;
+                        last_break = None;
+                        width_at_break = 0;
+                    }
+                    _ => {
+                        if let WrapStyle::Letter = &wrap {
+                            rows.push(std::mem::take(&mut current));
+                            width = 0;
+                        }
+                    }
+                }
+            }
+
+            width += advance;
+            current.push(glyph);
+        }
+        if !current.is_empty() {
+            rows.push(current);
+        }
+
+        align(rows, text, max_width, line_height, &h_align, &v_align)
+    }
+}
+
+fn align(
+    rows: Vec<Vec<Glyph>>,
+    text: &str,
+    max_width: i32,
+    line_height: i32,
+    h_align: &HorizontalAlign,
+    v_align: &VerticalAlign,
+) -> Vec<Line> {
+    let line_count = rows.len() as i32;
+    let block_height = line_count * line_height;
+    let y_shift = match v_align {
+        VerticalAlign::Top => 0,
+        VerticalAlign::Middle => -block_height / 2,
+        VerticalAlign::Bottom => -block_height,
+    };
+
+    rows.into_iter()
+        .enumerate()
+        .map(|(i, glyphs)| {
+            // Measure the visible extent: a wrapped line often ends in the
+            // space that created the break, whose advance must not skew the
+            // Center/Right offset.
+            let line_width = visible_width(&glyphs, text);
+            let x = match h_align {
+                HorizontalAlign::Left => 0,
+                HorizontalAlign::Center => (max_width - line_width) / 2,
+                HorizontalAlign::Right => max_width - line_width,
+            };
+            Line {
+                glyphs,
+                x,
+                y: y_shift + (i as i32 + 1) * line_height,
+            }
+        })
+        .collect()
+}
+
+/// Sum of glyph advances on a line excluding any run of trailing spaces, so
+/// alignment measures the visible text rather than the break whitespace.
+fn visible_width(glyphs: &[Glyph], text: &str) -> i32 {
+    let mut width = 0;
+    let mut trailing = 0;
+    for g in glyphs {
+        width += g.x_advance;
+        if is_space_at(text, g.cluster as usize) {
+            trailing += g.x_advance;
+        } else {
+            trailing = 0;
+        }
+    }
+    width - trailing
+}
+
+/// Whether the character originating the glyph at byte `offset` is a space
+/// that UAX #14 would treat as breakable whitespace (SP: tab or space).
+fn is_space_at(text: &str, offset: usize) -> bool {
+    matches!(
+        text.get(offset..).and_then(|s| s.chars().next()),
+        Some(' ' | '\t')
+    )
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum BreakKind {
+    /// A break must be taken here (LB4/LB5: BK, CR, LF).
+    Mandatory,
+    /// A break may be taken here.
+    Allowed,
+}
+
+/// UAX #14 line-break classes. Only the classes that participate in the
+/// pair table below are distinguished; everything else resolves to `AL`.
+#[derive(Clone, Copy, PartialEq)]
+enum BreakClass {
+    BK,
+    CR,
+    LF,
+    SP,
+    OP,
+    CL,
+    QU,
+    GL,
+    NS,
+    BA,
+    HY,
+    AL,
+    ID,
+    NU,
+    IS,
+    WJ,
+    ZW,
+    CM,
+}
+
+fn break_class(c: char) -> BreakClass {
+    use self::BreakClass::*;
+    match c {
+        '\u{000A}' => LF,
+        '\u{000D}' => CR,
+        '\u{000B}' | '\u{000C}' | '\u{2028}' | '\u{2029}' => BK,
+        '\u{0009}' | '\u{0020}' => SP,
+        '\u{00A0}' | '\u{2007}' | '\u{202F}' => GL, // non-breaking glue
+        '\u{2060}' | '\u{FEFF}' => WJ,
+        '\u{200B}' => ZW,
+        '\u{2010}' | '\u{2012}' | '\u{2013}' | '\u{2014}' => BA,
+        '\u{002D}' => HY,
+        '(' | '[' | '{' => OP,
+        ')' | ']' | '}' => CL,
+        '"' | '\'' | '\u{00AB}' | '\u{00BB}' | '\u{201C}' | '\u{201D}' => QU,
+        '!' | '?' | ';' | ':' | '\u{00A1}' | '\u{00BF}' => NS,
+        ',' | '.' | '/' => IS, // infix separators (LB25): stay inside numbers
+
+        '0'..='9' => NU,
+        _ if is_ideographic(c) => ID,
+        _ if is_combining(c) => CM,
+        _ => AL,
+    }
+}
+
+fn is_ideographic(c: char) -> bool {
+    matches!(c as u32,
+        0x1100..=0x11FF | // Hangul Jamo
+        0x2E80..=0x2FFF | // CJK radicals / Kangxi
+        0x3040..=0x9FFF | // Kana, CJK unified
+        0xAC00..=0xD7A3 | // Hangul syllables
+        0xF900..=0xFAFF | // CJK compat
+        0x20000..=0x2FFFD // CJK extension B+
+    )
+}
+
+fn is_combining(c: char) -> bool {
+    matches!(c as u32, 0x0300..=0x036F | 0x1AB0..=0x1AFF | 0x1DC0..=0x1DFF | 0x20D0..=0x20FF)
+}
+
+/// Action for an adjacent pair of break classes.
+#[derive(Clone, Copy, PartialEq)]
+enum Action {
+    /// Break is allowed directly between the two classes.
+    Direct,
+    /// Break is allowed only if a space intervened (`B SP × A`).
+    Indirect,
+    /// Break is prohibited between the two classes.
+    Prohibited,
+}
+
+/// Resolves the pair action for `before × after` following the spirit of the
+/// UAX #14 pair table. This is a rule-based condensation of the table rather
+/// than the full 43-row matrix, covering the classes produced above.
+fn pair_action(before: BreakClass, after: BreakClass) -> Action {
+    use self::Action::*;
+    use self::BreakClass::*;
+
+    // LB6/LB7: never break before a space or right after an explicit glue
+    // boundary; WJ forbids breaks on both sides (LB11).
+    if after == SP || after == WJ || before == WJ || after == CL || after == NS {
+        return Prohibited;
+    }
+    // LB8: ZW creates a break opportunity after it.
+    if before == ZW {
+        return Direct;
+    }
+    // LB9/LB10: combining marks stick to the preceding class.
+    if after == CM {
+        return Prohibited;
+    }
+    // LB12/LB12a: non-breaking glue forbids breaks on both sides.
+    if before == GL || after == GL {
+        return Prohibited;
+    }
+    // LB13: do not break before closing punctuation (handled above via CL).
+    // LB14: do not break after opening punctuation.
+    if before == OP {
+        return Prohibited;
+    }
+    // LB15/LB19: quotations bind to their neighbours.
+    if before == QU || after == QU {
+        return Prohibited;
+    }
+    // LB21: do not break before hyphen-like characters; a break directly
+    // after a hyphen is allowed.
+    if after == BA || after == HY {
+        return Prohibited;
+    }
+    if before == HY {
+        return Direct;
+    }
+    // LB25: keep numbers together, including their infix separators (',', '.',
+    // '/'): never break before a separator, nor between a separator and a
+    // following digit, so "3.14" and "1,000" stay on one line.
+    if after == IS {
+        return Prohibited;
+    }
+    if before == IS && after == NU {
+        return Prohibited;
+    }
+    if before == NU && after == NU {
+        return Prohibited;
+    }
+    // LB26/LB28: ideographs break freely from their neighbours (CJK wrapping).
+    if before == ID || after == ID {
+        return Direct;
+    }
+    // LB18: break opportunities exist after spaces (handled by Indirect).
+    if before == BA {
+        return Direct;
+    }
+    // Default (LB31): letters and numbers only break where a space allows it.
+    Indirect
+}
+
+use std::collections::HashMap;
+
+/// Computes the break opportunities for `text`, keyed by the byte offset of
+/// the character *before which* a break may (or must) occur.
+fn break_opportunities(text: &str) -> HashMap<usize, BreakKind> {
+    use self::BreakClass::*;
+
+    let mut out = HashMap::new();
+    let mut chars = text.char_indices();
+
+    // LB2: never break at the start of text; the first class simply seeds the
+    // pair scan below.
+    let mut cls = match chars.next() {
+        Some((_, c)) => break_class(c),
+        None => return out,
+    };
+    let mut last_was_space = cls == SP;
+
+    for (offset, c) in chars {
+        let cb = break_class(c);
+
+        // LB4/LB5: mandatory break after BK, CR (not before LF), LF.
+        if cls == BK || (cls == CR && cb != LF) || cls == LF {
+            out.insert(offset, BreakKind::Mandatory);
+            cls = cb;
+            last_was_space = cls == SP;
+            continue;
+        }
+
+        // Never break before a hard line break; it is handled on its own turn.
+        if cb == BK || cb == CR || cb == LF {
+            cls = cb;
+            last_was_space = false;
+            continue;
+        }
+
+        if cb == SP {
+            last_was_space = true;
+            continue; // LB7: keep `cls` at the last non-space class.
+        }
+
+        match pair_action(cls, cb) {
+            Action::Direct => {
+                out.insert(offset, BreakKind::Allowed);
+            }
+            Action::Indirect => {
+                if last_was_space {
+                    out.insert(offset, BreakKind::Allowed);
+                }
+            }
+            Action::Prohibited => {}
+        }
+
+        cls = cb;
+        last_was_space = false;
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // One unit-advance glyph per character, with `cluster` set to the byte
+    // offset of the originating character, mirroring how `Raqm::glyphs()`
+    // reports clusters for the layout logic under test.
+    fn glyphs(text: &str) -> Vec<Glyph> {
+        text.char_indices()
+            .map(|(i, _)| Glyph {
+                index: 0,
+                x_advance: 1,
+                y_advance: 0,
+                x_offset: 0,
+                y_offset: 0,
+                cluster: i as u32,
+                face: std::ptr::null_mut(),
+            })
+            .collect()
+    }
+
+    // Renders each wrapped row back to the substring of `text` it covers.
+    fn rows(text: &str, max_width: i32, wrap: WrapStyle) -> Vec<String> {
+        LineLayout::new(text, glyphs(text), max_width, 10)
+            .wrap(wrap)
+            .lines()
+            .into_iter()
+            .map(|line| {
+                line.glyphs
+                    .iter()
+                    .map(|g| text[g.cluster as usize..].chars().next().unwrap())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn wraps_at_word_start_overflow() {
+        // "ab cd " fits in 6 units; the first glyph of "ef" overflows and must
+        // retreat to its own break, not the previous word's.
+        assert_eq!(rows("ab cd ef", 6, WrapStyle::Word), vec!["ab cd ", "ef"]);
+    }
+
+    #[test]
+    fn wraps_trailing_word_that_overflows() {
+        // The final word's first glyph triggers overflow with no earlier break
+        // on its row; it must still move to a new line.
+        assert_eq!(rows("aaaa b", 5, WrapStyle::Word), vec!["aaaa ", "b"]);
+    }
+
+    #[test]
+    fn breaks_at_mandatory_line_feed() {
+        assert_eq!(rows("ab\ncd", 100, WrapStyle::Word), vec!["ab\n", "cd"]);
+    }
+
+    #[test]
+    fn breaks_at_mandatory_vertical_tab() {
+        assert_eq!(rows("a\u{000B}b", 100, WrapStyle::Word), vec!["a\u{000B}", "b"]);
+    }
+
+    #[test]
+    fn keeps_numbers_with_infix_separators() {
+        // "3.14" has no internal break opportunity, so it stays whole and the
+        // only break taken is the space before it (LB25).
+        assert_eq!(rows("x 3.14", 3, WrapStyle::Word), vec!["x ", "3.14"]);
+    }
+
+    #[test]
+    fn right_align_ignores_trailing_space() {
+        // Line one ends in the break space; its advance must not count toward
+        // the right-alignment offset.
+        let lines = LineLayout::new("ab cd", glyphs("ab cd"), 3, 10)
+            .horizontal_align(HorizontalAlign::Right)
+            .lines();
+        assert_eq!(lines[0].glyphs.len(), 3); // "ab "
+        assert_eq!(lines[0].x, 1); // 3 - visible width 2, trailing space trimmed
+    }
+}